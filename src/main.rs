@@ -5,10 +5,16 @@ use std::{
     collections::HashMap,
     env,
     ffi::OsString,
-    path::PathBuf,
+    fs,
+    path::{Path, PathBuf},
     process::{self, Command, ExitStatus},
 };
 
+const CONFIG_NAME: &str = "cargo-android.toml";
+
+// Hidden first argument that switches us into run-on-device mode.
+const RUN_ON_DEVICE_ARG: &str = "--run-on-device";
+
 #[cfg(target_os = "linux")]
 const NDK_OS: &str = "linux";
 #[cfg(target_os = "macos")]
@@ -16,28 +22,182 @@ const NDK_OS: &str = "darwin";
 #[cfg(target_os = "windows")]
 const NDK_OS: &str = "windows";
 
-#[cfg(not(target_os = "windows"))]
-const CLANG_SUFFIX: &str = "";
-#[cfg(target_os = "windows")]
-const CLANG_SUFFIX: &str = ".cmd";
-
 #[cfg(not(target_os = "windows"))]
 const EXE_SUFFIX: &str = "";
 #[cfg(target_os = "windows")]
 const EXE_SUFFIX: &str = ".exe";
 
-fn get_android_env(target: &str) -> Result<HashMap<String, OsString>, String> {
-    let ndk_dir = env::var_os("ANDROID_NDK_ROOT")
-        .map(PathBuf::from)
-        .ok_or("ANDROID_NDK_ROOT must be set when building for Android")?;
+#[derive(Default)]
+struct TargetConfig {
+    api: Option<u8>,
+    extra_clang_args: Vec<String>,
+}
 
-    let upper_target = target.to_ascii_uppercase().replace('-', "_");
-    let ndk_target = match target {
-        "armv7-linux-androideabi" | "thumbv7neon-linux-androideabi" => "armv7a-linux-androideabi",
-        t => t,
-    };
+#[derive(Default)]
+struct Config {
+    ndk: Option<PathBuf>,
+    api: Option<u8>,
+    targets: HashMap<String, TargetConfig>,
+}
+
+fn parse_string(value: &str) -> Result<String, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map(str::to_owned)
+        .ok_or_else(|| format!("Expected a quoted string: {value}"))
+}
+
+fn parse_string_array(value: &str) -> Result<Vec<String>, String> {
+    let inner = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(|| format!("Expected an array: {value}"))?
+        .trim();
+
+    if inner.is_empty() {
+        return Ok(vec![]);
+    }
+
+    inner
+        .split(',')
+        .map(|item| parse_string(item.trim()))
+        .collect()
+}
+
+// Parse the small subset of TOML used by the config: top-level `ndk`/`api` and
+// `[target.<triple>]` tables with `api`/`extra-clang-args`.
+fn parse_config(contents: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    // `None` is the top-level table; `Some(triple)` is `[target.<triple>]`.
+    let mut section: Option<String> = None;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let header = header.trim();
+            if let Some(triple) = header.strip_prefix("target.") {
+                config.targets.entry(triple.to_owned()).or_default();
+                section = Some(triple.to_owned());
+            } else {
+                return Err(format!("Unknown section: [{header}]"));
+            }
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Expected key = value: {line}"))?;
+        let key = key.trim();
+        let value = value.trim();
+
+        match &section {
+            None => match key {
+                "ndk" => config.ndk = Some(PathBuf::from(parse_string(value)?)),
+                "api" => {
+                    config.api = Some(value.parse().map_err(|_| format!("Invalid api: {value}"))?)
+                }
+                _ => return Err(format!("Unknown key: {key}")),
+            },
+            Some(triple) => {
+                let target = config.targets.entry(triple.clone()).or_default();
+                match key {
+                    "api" => {
+                        target.api =
+                            Some(value.parse().map_err(|_| format!("Invalid api: {value}"))?)
+                    }
+                    "extra-clang-args" => target.extra_clang_args = parse_string_array(value)?,
+                    _ => return Err(format!("Unknown key: {key}")),
+                }
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+// Search upward from the working directory for a `cargo-android.toml`, the same
+// way Cargo discovers its own config.
+fn find_config() -> Result<Config, String> {
+    let cwd = env::current_dir().map_err(|e| format!("Failed to get working directory: {e}"))?;
+
+    for dir in cwd.ancestors() {
+        let path = dir.join(CONFIG_NAME);
+        if path.exists() {
+            let contents =
+                fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let mut config =
+                parse_config(&contents).map_err(|e| format!("{}: {e}", path.display()))?;
+
+            // Resolve a relative ndk path against the config file's directory
+            // so a checked-in config works from any subdirectory.
+            if let Some(ndk) = &config.ndk {
+                if ndk.is_relative() {
+                    config.ndk = Some(dir.join(ndk));
+                }
+            }
+
+            return Ok(config);
+        }
+    }
+
+    Ok(Config::default())
+}
 
-    let mut toolchain_dir = ndk_dir.clone();
+// Pick the highest-versioned subdirectory of `lib/clang`, comparing entries as
+// dotted version numbers rather than trusting the order read_dir returns.
+fn highest_clang_version(clang_dir: &Path) -> Result<OsString, String> {
+    clang_dir
+        .read_dir()
+        .map_err(|e| format!("Failed to list directory: {clang_dir:?}: {e}"))?
+        .filter_map(|r| r.ok())
+        .filter_map(|e| {
+            let name = e.file_name();
+            let version = name
+                .to_str()?
+                .split('.')
+                .map(|p| p.parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            Some((version, name))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, name)| name)
+        .ok_or_else(|| format!("Missing clang version: {clang_dir:?}"))
+}
+
+// Parse the NDK's major version from `source.properties`'s `Pkg.Revision`
+// (e.g. `25.2.9519653` -> `25`). Returns `None` if the file is missing or
+// doesn't contain a parseable revision, e.g. for vendored toolchains.
+fn ndk_major_version(ndk_dir: &Path) -> Option<u32> {
+    let contents = fs::read_to_string(ndk_dir.join("source.properties")).ok()?;
+
+    for line in contents.lines() {
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "Pkg.Revision" {
+                return value.trim().split('.').next()?.parse().ok();
+            }
+        }
+    }
+
+    None
+}
+
+fn resolve_ndk_dir(config: &Config) -> Result<PathBuf, String> {
+    env::var_os("ANDROID_NDK_ROOT")
+        .map(PathBuf::from)
+        .or_else(|| config.ndk.clone())
+        .ok_or_else(|| {
+            "ANDROID_NDK_ROOT must be set (or `ndk` in cargo-android.toml) when building for Android"
+                .to_owned()
+        })
+}
+
+fn ndk_toolchain_dir(ndk_dir: &Path) -> Result<PathBuf, String> {
+    let mut toolchain_dir = ndk_dir.to_path_buf();
     toolchain_dir.push("toolchains");
     toolchain_dir.push("llvm");
     toolchain_dir.push("prebuilt");
@@ -47,12 +207,41 @@ fn get_android_env(target: &str) -> Result<HashMap<String, OsString>, String> {
         return Err(format!("Toolchain directory not found: {toolchain_dir:?}"));
     }
 
+    Ok(toolchain_dir)
+}
+
+// Map a Rust Android target triple to its Android ABI directory name.
+fn android_abi(target: &str) -> Option<&'static str> {
+    match target {
+        "aarch64-linux-android" => Some("arm64-v8a"),
+        "armv7-linux-androideabi" | "thumbv7neon-linux-androideabi" => Some("armeabi-v7a"),
+        "i686-linux-android" => Some("x86"),
+        "x86_64-linux-android" => Some("x86_64"),
+        _ => None,
+    }
+}
+
+fn get_android_env(target: &str) -> Result<HashMap<String, OsString>, String> {
+    let config = find_config()?;
+    let target_config = config.targets.get(target);
+
+    let ndk_dir = resolve_ndk_dir(&config)?;
+
+    let upper_target = target.to_ascii_uppercase().replace('-', "_");
+    let ndk_target = match target {
+        "armv7-linux-androideabi" | "thumbv7neon-linux-androideabi" => "armv7a-linux-androideabi",
+        t => t,
+    };
+
+    let toolchain_dir = ndk_toolchain_dir(&ndk_dir)?;
     let sysroot_dir = toolchain_dir.join("sysroot");
 
     let api = if let Some(v) = env::var_os("ANDROID_API") {
         v.to_str()
             .and_then(|s| s.parse::<u8>().ok())
             .ok_or_else(|| format!("Invalid ANDROID_API: {v:?}"))?
+    } else if let Some(api) = target_config.and_then(|t| t.api).or(config.api) {
+        api
     } else {
         let mut lib_dir = sysroot_dir.clone();
         lib_dir.push("usr");
@@ -74,16 +263,39 @@ fn get_android_env(target: &str) -> Result<HashMap<String, OsString>, String> {
     ar.push("bin");
     ar.push(format!("llvm-ar{EXE_SUFFIX}"));
 
+    // Invoke clang directly with an explicit --target instead of the NDK's
+    // soon-to-be-removed `{ndk_target}{api}-clang` wrapper scripts.
     let mut clang = toolchain_dir.clone();
     clang.push("bin");
-    clang.push(format!("{ndk_target}{api}-clang{CLANG_SUFFIX}"));
+    clang.push(format!("clang{EXE_SUFFIX}"));
+
+    let clang_target = format!("--target={ndk_target}{api}");
+
+    // Extra clang args configured for this target are appended to both the C
+    // compiler flags and bindgen's clang args so headers and sources see them.
+    let extra_clang_args = target_config
+        .map(|t| t.extra_clang_args.as_slice())
+        .unwrap_or(&[]);
 
     let mut vars = HashMap::new();
     vars.insert(format!("AR_{target}"), ar.into_os_string());
     vars.insert(format!("CC_{target}"), clang.as_os_str().to_owned());
+    vars.insert(format!("CFLAGS_{target}"), {
+        let mut v = OsString::from(&clang_target);
+        for arg in extra_clang_args {
+            v.push(" ");
+            v.push(arg);
+        }
+        v
+    });
     vars.insert(format!("BINDGEN_EXTRA_CLANG_ARGS_{target}"), {
-        let mut v = OsString::from("--sysroot=");
-        v.push(sysroot_dir);
+        let mut v = OsString::from(&clang_target);
+        v.push(" --sysroot=");
+        v.push(&sysroot_dir);
+        for arg in extra_clang_args {
+            v.push(" ");
+            v.push(arg);
+        }
         v
     });
     vars.insert(
@@ -91,58 +303,94 @@ fn get_android_env(target: &str) -> Result<HashMap<String, OsString>, String> {
         clang.into_os_string(),
     );
 
-    // Work around https://github.com/rust-lang/rust/issues/109717.
-    if target == "x86_64-linux-android" {
+    let mut rustflags = vec![];
+
+    // Global flags completely override CARGO_TARGET_<target>_RUSTFLAGS, so
+    // we have to append to the global flags instead of using target flags.
+    // Cargo only supports UTF-8 for these variables, so we don't worry
+    // about OsString here.
+    if let Ok(flags) = env::var("CARGO_ENCODED_RUSTFLAGS") {
+        rustflags.extend(flags.split('\x1f').map(str::to_string));
+    } else if let Ok(flags) = env::var("RUSTFLAGS") {
+        rustflags.extend(
+            flags
+                .split(' ')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        );
+    }
+
+    // The linker is plain clang, so it needs the target triple too.
+    rustflags.push(format!("-Clink-arg={clang_target}"));
+
+    // Work around https://github.com/rust-lang/rust/issues/109717. When
+    // compiler-builtins can't satisfy symbols like `__extenddftf2`, linking the
+    // NDK's own `libclang_rt.builtins-<arch>-android.a` fixes it. This isn't
+    // x86_64-specific, so do it for every Android target whose archive exists.
+    {
         let mut clang_dir = toolchain_dir.clone();
         clang_dir.push("lib");
         clang_dir.push("clang");
 
-        let clang_version = clang_dir
-            .read_dir()
-            .and_then(|mut d| d.next().transpose())
-            .map_err(|e| format!("Failed to list directory: {clang_dir:?}: {e}"))?
-            .ok_or_else(|| format!("Missing clang version: {clang_dir:?}"))?
-            .file_name();
+        let clang_version = highest_clang_version(&clang_dir)?;
+
+        let clang_rt_arch = match target {
+            "armv7-linux-androideabi" | "thumbv7neon-linux-androideabi" => "arm",
+            t => t.split('-').next().unwrap_or(t),
+        };
+        let clang_rt_name = format!("clang_rt.builtins-{clang_rt_arch}-android");
 
         let mut clang_rt_dir = clang_dir.clone();
         clang_rt_dir.push(clang_version);
         clang_rt_dir.push("lib");
         clang_rt_dir.push("linux");
 
-        let clang_rt_dir = clang_rt_dir
+        // Skip silently if the NDK doesn't ship this archive for the target.
+        if clang_rt_dir.join(format!("lib{clang_rt_name}.a")).exists() {
+            let clang_rt_dir = clang_rt_dir
+                .into_os_string()
+                .into_string()
+                .map_err(|p| format!("Invalid UTF-8: {p:?}"))?;
+
+            rustflags.push("-L".into());
+            rustflags.push(clang_rt_dir);
+            rustflags.push("-l".into());
+            rustflags.push(format!("static={clang_rt_name}"));
+        }
+    }
+
+    // NDK r23 replaced libgcc with libunwind, so crates that still link against
+    // `-lgcc` fail with missing symbols. The standard fix is to put a `libgcc.a`
+    // linker script containing `INPUT(-lunwind)` on the search path. We write it
+    // on every run to one fixed directory under the system temp dir and point
+    // the link search path at it.
+    if ndk_major_version(&ndk_dir).is_some_and(|v| v >= 23) {
+        let mut libgcc_dir = env::temp_dir();
+        libgcc_dir.push("cargo-android");
+        libgcc_dir.push("libgcc");
+
+        fs::create_dir_all(&libgcc_dir)
+            .map_err(|e| format!("Failed to create directory: {libgcc_dir:?}: {e}"))?;
+
+        let libgcc = libgcc_dir.join("libgcc.a");
+        fs::write(&libgcc, "INPUT(-lunwind)\n")
+            .map_err(|e| format!("Failed to write: {libgcc:?}: {e}"))?;
+
+        let libgcc_dir = libgcc_dir
             .into_os_string()
             .into_string()
             .map_err(|p| format!("Invalid UTF-8: {p:?}"))?;
 
-        let mut rustflags = vec![];
-
-        // Global flags completely override CARGO_TARGET_<target>_RUSTFLAGS, so
-        // we have to append to the global flags instead of using target flags.
-        // Cargo only supports UTF-8 for these variables, so we don't worry
-        // about OsString here.
-        if let Ok(flags) = env::var("CARGO_ENCODED_RUSTFLAGS") {
-            rustflags.extend(flags.split('\x1f').map(str::to_string));
-        } else if let Ok(flags) = env::var("RUSTFLAGS") {
-            rustflags.extend(
-                flags
-                    .split(' ')
-                    .map(str::trim)
-                    .filter(|s| !s.is_empty())
-                    .map(str::to_string),
-            );
-        }
-
         rustflags.push("-L".into());
-        rustflags.push(clang_rt_dir);
-        rustflags.push("-l".into());
-        rustflags.push("static=clang_rt.builtins-x86_64-android".into());
-
-        vars.insert(
-            format!("CARGO_ENCODED_RUSTFLAGS"),
-            rustflags.join("\x1f").into(),
-        );
+        rustflags.push(libgcc_dir);
     }
 
+    vars.insert(
+        "CARGO_ENCODED_RUSTFLAGS".to_owned(),
+        rustflags.join("\x1f").into(),
+    );
+
     Ok(vars)
 }
 
@@ -179,6 +427,24 @@ fn main_wrapper() -> Result<ExitStatus, String> {
     if let Some(t) = &target {
         if t.contains("android") {
             command.envs(get_android_env(t)?);
+
+            // For subcommands that execute the built binary, run it on a device
+            // by pointing Cargo's runner back at ourselves in device mode.
+            let subcommand = env::args_os().nth(2);
+            let subcommand = subcommand.as_deref().and_then(|s| s.to_str());
+            if matches!(subcommand, Some("run" | "test" | "bench")) {
+                let exe = env::current_exe()
+                    .map_err(|e| format!("Failed to get current executable: {e}"))?;
+                let upper_target = t.to_ascii_uppercase().replace('-', "_");
+
+                // Cargo splits the runner on whitespace and execs the first
+                // token verbatim (no shell unescaping), so an exe path
+                // containing spaces is unsupported here.
+                let mut runner = exe.into_os_string();
+                runner.push(" ");
+                runner.push(RUN_ON_DEVICE_ARG);
+                command.env(format!("CARGO_TARGET_{upper_target}_RUNNER"), runner);
+            }
         }
     }
 
@@ -188,6 +454,203 @@ fn main_wrapper() -> Result<ExitStatus, String> {
     Ok(status)
 }
 
+fn adb_command() -> Command {
+    let mut command = Command::new("adb");
+    if let Some(serial) = env::var_os("ANDROID_SERIAL") {
+        command.arg("-s").arg(serial);
+    }
+    command
+}
+
+fn adb_run(args: &[OsString]) -> Result<(), String> {
+    let status = adb_command()
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run adb: {e}"))?;
+    if !status.success() {
+        return Err(format!("adb {args:?} failed: {status}"));
+    }
+    Ok(())
+}
+
+// Wrap a string in single quotes for safe use in an adb shell command line.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn run_on_device() -> Result<ExitStatus, String> {
+    let mut args = env::args_os().skip(2);
+    let executable = PathBuf::from(args.next().ok_or("No executable to run on device")?);
+    let run_args = args.collect::<Vec<_>>();
+
+    let file_name = executable
+        .file_name()
+        .ok_or_else(|| format!("Invalid executable path: {executable:?}"))?
+        .to_string_lossy()
+        .into_owned();
+
+    let remote_dir = format!("/data/local/tmp/cargo-android-{}", process::id());
+    let remote_bin = format!("{remote_dir}/{file_name}");
+
+    adb_run(&[
+        "shell".into(),
+        "mkdir".into(),
+        "-p".into(),
+        remote_dir.clone().into(),
+    ])?;
+
+    // Push the binary along with any shared libraries sitting next to it so the
+    // process can resolve them via LD_LIBRARY_PATH.
+    let mut to_push = vec![executable.clone()];
+    if let Some(parent) = executable.parent() {
+        if let Ok(entries) = parent.read_dir() {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) == Some("so") {
+                    to_push.push(path);
+                }
+            }
+        }
+    }
+    for path in &to_push {
+        adb_run(&[
+            "push".into(),
+            path.clone().into_os_string(),
+            remote_dir.clone().into(),
+        ])?;
+    }
+
+    adb_run(&[
+        "shell".into(),
+        "chmod".into(),
+        "+x".into(),
+        remote_bin.clone().into(),
+    ])?;
+
+    // Assemble the remote command line, shell-quoting each argument.
+    let mut remote_cmd = format!("LD_LIBRARY_PATH={remote_dir} {}", shell_quote(&remote_bin));
+    for arg in &run_args {
+        remote_cmd.push(' ');
+        remote_cmd.push_str(&shell_quote(&arg.to_string_lossy()));
+    }
+
+    // adb shell forwards the remote exit code, including 128+signal for crashes.
+    let status = adb_command()
+        .arg("shell")
+        .arg(&remote_cmd)
+        .status()
+        .map_err(|e| format!("Failed to run adb shell: {e}"))?;
+
+    // Best-effort cleanup regardless of the run's outcome.
+    let _ = adb_run(&["shell".into(), "rm".into(), "-rf".into(), remote_dir.into()]);
+
+    Ok(status)
+}
+
+// Build each Android target in release mode and collect the resulting cdylib/
+// staticlib artifacts into a `jniLibs/<abi>/` tree. Shared libraries are
+// stripped; static libraries are copied as-is.
+fn package() -> Result<ExitStatus, String> {
+    let config = find_config()?;
+    let ndk_dir = resolve_ndk_dir(&config)?;
+    let toolchain_dir = ndk_toolchain_dir(&ndk_dir)?;
+    let strip = toolchain_dir
+        .join("bin")
+        .join(format!("llvm-strip{EXE_SUFFIX}"));
+
+    // Pull the --target values out of the argument list; everything else is
+    // forwarded to `cargo build` verbatim.
+    let mut targets = vec![];
+    let mut passthrough = vec![];
+    let mut args = env::args_os().skip(3);
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_str();
+        if arg_str == Some("--target") {
+            let value = args.next().ok_or("--target requires a value")?;
+            let value = value
+                .to_str()
+                .ok_or_else(|| format!("Invalid UTF-8: {value:?}"))?;
+            targets.push(value.to_owned());
+        } else if let Some(value) = arg_str.and_then(|s| s.strip_prefix("--target=")) {
+            targets.push(value.to_owned());
+        } else {
+            passthrough.push(arg);
+        }
+    }
+
+    if targets.is_empty() {
+        return Err("At least one --target is required for `package`".to_owned());
+    }
+
+    let cargo = env::var_os("CARGO").ok_or("CARGO must be set")?;
+    let target_dir = env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"));
+    let jni_libs_dir = target_dir.join("jniLibs");
+
+    let mut last_status = None;
+
+    for target in &targets {
+        let abi = android_abi(target)
+            .ok_or_else(|| format!("Unknown Android ABI for target: {target}"))?;
+
+        let mut command = Command::new(&cargo);
+        command
+            .arg("build")
+            .arg("--release")
+            .arg("--target")
+            .arg(target);
+        command.args(&passthrough);
+        command.envs(get_android_env(target)?);
+
+        let status = command.status().map_err(|e| format!("{command:?}: {e}"))?;
+        if !status.success() {
+            return Ok(status);
+        }
+        last_status = Some(status);
+
+        let abi_dir = jni_libs_dir.join(abi);
+        fs::create_dir_all(&abi_dir)
+            .map_err(|e| format!("Failed to create directory: {abi_dir:?}: {e}"))?;
+
+        let release_dir = target_dir.join(target).join("release");
+        for entry in release_dir
+            .read_dir()
+            .map_err(|e| format!("{release_dir:?}: {e}"))?
+        {
+            let path = entry.map_err(|e| format!("{release_dir:?}: {e}"))?.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            let is_shared = path.extension().and_then(|e| e.to_str()) == Some("so");
+            let is_static = path.extension().and_then(|e| e.to_str()) == Some("a");
+            if !is_shared && !is_static {
+                continue;
+            }
+
+            let dest = abi_dir.join(path.file_name().unwrap());
+            fs::copy(&path, &dest)
+                .map_err(|e| format!("Failed to copy {path:?} -> {dest:?}: {e}"))?;
+
+            // Strip shared libraries to keep the packaged output small.
+            if is_shared {
+                let status = Command::new(&strip)
+                    .arg(&dest)
+                    .status()
+                    .map_err(|e| format!("Failed to run {strip:?}: {e}"))?;
+                if !status.success() {
+                    return Err(format!("llvm-strip failed on {dest:?}: {status}"));
+                }
+            }
+        }
+    }
+
+    eprintln!("Packaged jniLibs to {}", jni_libs_dir.display());
+
+    Ok(last_status.unwrap())
+}
+
 fn get_exit_code(status: ExitStatus) -> i32 {
     if let Some(code) = status.code() {
         return code;
@@ -206,7 +669,21 @@ fn get_exit_code(status: ExitStatus) -> i32 {
 }
 
 fn main() {
-    let code = match main_wrapper() {
+    let device_mode = env::args_os()
+        .nth(1)
+        .is_some_and(|a| a == RUN_ON_DEVICE_ARG);
+
+    let is_package = env::args_os().nth(2).is_some_and(|a| a == "package");
+
+    let result = if device_mode {
+        run_on_device()
+    } else if is_package {
+        package()
+    } else {
+        main_wrapper()
+    };
+
+    let code = match result {
         Ok(status) => get_exit_code(status),
         Err(e) => {
             eprintln!("{e}");
@@ -216,3 +693,67 @@ fn main() {
 
     process::exit(code);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("foo"), "'foo'");
+        assert_eq!(shell_quote("with space"), "'with space'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_parse_string_array() {
+        assert_eq!(parse_string_array("[]").unwrap(), Vec::<String>::new());
+        assert_eq!(parse_string_array(r#"["a"]"#).unwrap(), ["a"]);
+        assert_eq!(
+            parse_string_array(r#"["-I", "/usr/include"]"#).unwrap(),
+            ["-I", "/usr/include"]
+        );
+        assert!(parse_string_array("not-an-array").is_err());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(
+            r#"
+            # comment
+            ndk = "/opt/ndk"
+            api = 24
+
+            [target.aarch64-linux-android]
+            api = 21
+            extra-clang-args = ["-DFOO"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.ndk, Some(PathBuf::from("/opt/ndk")));
+        assert_eq!(config.api, Some(24));
+
+        let target = &config.targets["aarch64-linux-android"];
+        assert_eq!(target.api, Some(21));
+        assert_eq!(target.extra_clang_args, ["-DFOO"]);
+
+        assert!(parse_config("nonsense").is_err());
+        assert!(parse_config("[unknown]").is_err());
+    }
+
+    #[test]
+    fn test_highest_clang_version() {
+        let dir = env::temp_dir().join(format!("cargo-android-test-{}", process::id()));
+        for version in ["14.0.6", "17.0.2", "9.0.8"] {
+            fs::create_dir_all(dir.join(version)).unwrap();
+        }
+        fs::write(dir.join("not-a-version"), "").unwrap();
+
+        let highest = highest_clang_version(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(highest, OsString::from("17.0.2"));
+    }
+}